@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{Config, Todo, validate_title};
+
+/// How a foreign export's items map onto our fields.
+struct Format {
+    // The top-level array holding the tasks.
+    items_key: &'static str,
+    title_key: &'static str,
+    completed_key: &'static str,
+}
+
+const TODOIST: Format = Format {
+    items_key: "items",
+    title_key: "content",
+    completed_key: "checked",
+};
+
+const TRELLO: Format = Format {
+    items_key: "cards",
+    title_key: "name",
+    completed_key: "dueComplete",
+};
+
+#[derive(Debug, Serialize)]
+pub struct ImportResult {
+    pub imported: Vec<Todo>,
+    // Fields present in the export that have no equivalent in our model, as
+    // dotted paths (e.g. `items.labels`).
+    pub unmapped_fields: Vec<String>,
+}
+
+/// Converts a Todoist or Trello JSON export into new todos. Titles go through
+/// the same validation as create.
+pub fn parse(config: &Config, format: &str, body: &Value) -> Result<ImportResult, String> {
+    let format = match format {
+        "todoist" => &TODOIST,
+        "trello" => &TRELLO,
+        _ => return Err(format!("Unsupported import format {}", format)),
+    };
+    let Some(root) = body.as_object() else {
+        return Err("Import body must be a JSON object".to_string());
+    };
+    let Some(items) = root.get(format.items_key).and_then(Value::as_array) else {
+        return Err(format!("Import body must contain a {} array", format.items_key));
+    };
+
+    let mut unmapped_fields: BTreeSet<String> = root
+        .keys()
+        .filter(|k| *k != format.items_key)
+        .cloned()
+        .collect();
+    let mut imported = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let Some(item) = item.as_object() else {
+            return Err(format!("{}[{}] must be an object", format.items_key, index));
+        };
+        let Some(title) = item.get(format.title_key).and_then(Value::as_str) else {
+            return Err(format!("{}[{}] is missing {}", format.items_key, index, format.title_key));
+        };
+        let title = validate_title(config, title.trim().to_string()).map_err(|errors| {
+            format!("{}[{}].{}: {}", format.items_key, index, format.title_key, errors.join("; "))
+        })?;
+
+        let completed = match item.get(format.completed_key) {
+            None => false,
+            Some(value) => value.as_bool().ok_or_else(|| {
+                format!("{}[{}].{} must be a boolean", format.items_key, index, format.completed_key)
+            })?,
+        };
+
+        let mut todo = Todo::new(title);
+        todo.completed = completed;
+        imported.push(todo);
+
+        unmapped_fields.extend(
+            item.keys()
+                .filter(|k| *k != format.title_key && *k != format.completed_key)
+                .map(|k| format!("{}.{}", format.items_key, k)),
+        );
+    }
+
+    Ok(ImportResult {
+        imported,
+        unmapped_fields: unmapped_fields.into_iter().collect(),
+    })
+}
+
+/// A row of a CSV import. Only `title` is required; a row with an `id` updates
+/// that existing todo instead of creating a new one.
+#[derive(Debug, Deserialize)]
+pub struct CsvRow {
+    pub id: Option<String>,
+    pub title: String,
+    pub completed: Option<bool>,
+}
+
+/// Parses a CSV body with a header row, reporting the first bad row by its
+/// 1-based position among the data rows (the header isn't counted). Titles go
+/// through the same validation as create.
+pub fn parse_csv(config: &Config, body: &[u8]) -> Result<Vec<CsvRow>, String> {
+    let mut reader = csv::ReaderBuilder::new().trim(csv::Trim::All).from_reader(body);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .clone();
+    if !headers.iter().any(|h| h == "title") {
+        return Err("CSV header must contain a title column".to_string());
+    }
+
+    let mut rows = Vec::new();
+    for (index, record) in reader.records().enumerate() {
+        let row_number = index + 1;
+        let record = record.map_err(|e| format!("Row {}: {}", row_number, e))?;
+        let mut row: CsvRow = record
+            .deserialize(Some(&headers))
+            .map_err(|e| format!("Row {}: {}", row_number, e))?;
+        row.title = validate_title(config, row.title)
+            .map_err(|errors| format!("Row {}: {}", row_number, errors.join("; ")))?;
+        rows.push(row);
+    }
+
+    Ok(rows)
+}
@@ -1,14 +1,60 @@
-use actix_web::{web, App, HttpResponse, HttpServer, Responder, post, get, put, delete};
+use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer, Responder, post, get, put, delete};
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header;
+use actix_web::middleware::{from_fn, DefaultHeaders, Next, NormalizePath};
 use serde::{Deserialize, Serialize};
-use std::sync::{Mutex, Arc};
+use serde::de::DeserializeOwned;
+use std::sync::{Mutex, MutexGuard, Arc};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Instant, SystemTime};
 use uuid::Uuid;
 use dotenvy::dotenv;
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod import;
+mod select;
+mod snapshot;
+
+use snapshot::Snapshot;
+
+// Async imports take the store lock once per chunk of this many todos.
+const IMPORT_CHUNK_SIZE: usize = 500;
+
+// Async imports may be this many times MAX_BULK_SIZE, since they don't hold the
+// lock for the whole batch.
+const ASYNC_IMPORT_BULK_MULTIPLIER: usize = 100;
+
+// How many finished import jobs stay queryable before the oldest is evicted.
+const MAX_FINISHED_IMPORT_JOBS: usize = 100;
+
+// Bump whenever a response shape changes in a way clients must notice.
+const SCHEMA_VERSION: &str = "1";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 struct Todo {
     id: String,
     title: String,
     completed: bool,
+    #[serde(default)]
+    related: Vec<String>,
+    // Notes on notable state changes, oldest first (e.g. why it was reopened).
+    #[serde(default)]
+    history: Vec<String>,
+}
+
+impl Todo {
+    fn new(title: String) -> Self {
+        Todo {
+            id: Uuid::new_v4().to_string(),
+            title,
+            completed: false,
+            related: Vec::new(),
+            history: Vec::new(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize,  Deserialize)]
@@ -21,24 +67,462 @@ struct UpdateTodoRequest {
     title: String,
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictCreateTodoRequest {
+    title: String,
+}
+
+impl From<StrictCreateTodoRequest> for CreateTodoRequest {
+    fn from(req: StrictCreateTodoRequest) -> Self {
+        CreateTodoRequest { title: req.title }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictUpdateTodoRequest {
+    title: String,
+}
+
+impl From<StrictUpdateTodoRequest> for UpdateTodoRequest {
+    fn from(req: StrictUpdateTodoRequest) -> Self {
+        UpdateTodoRequest { title: req.title }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    // Reject request bodies containing fields the API does not know about.
+    strict_fields: bool,
+    // Strip control/zero-width characters and collapse whitespace in titles.
+    sanitize_titles: bool,
+    // Base URL deep links to todos are built from.
+    app_base_url: String,
+    // Where the store is periodically snapshotted and restored from at startup.
+    snapshot_path: PathBuf,
+    // Gzip snapshots on disk; loading detects either format.
+    snapshot_compress: bool,
+    // No snapshots are taken when this is 0.
+    snapshot_interval_secs: u64,
+    // Largest number of items a single bulk request may touch.
+    max_bulk_size: usize,
+    // How long deleted todos can be restored; 0 deletes them outright.
+    undo_window_secs: u64,
+    // Display words for the completed flag; the stored field stays a bool.
+    status_labels: StatusLabels,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+struct StatusLabels {
+    completed: String,
+    incomplete: String,
+}
+
+impl Default for StatusLabels {
+    fn default() -> Self {
+        StatusLabels {
+            completed: "completed".to_string(),
+            incomplete: "open".to_string(),
+        }
+    }
+}
+
+impl StatusLabels {
+    // Parses `completed=Done,incomplete=Open`; keys left out keep their defaults.
+    fn parse(value: &str) -> Self {
+        let mut labels = StatusLabels::default();
+        for pair in value.split(',').filter(|p| !p.trim().is_empty()) {
+            match pair.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("completed", label)) if !label.is_empty() => labels.completed = label.to_string(),
+                Some(("incomplete", label)) if !label.is_empty() => labels.incomplete = label.to_string(),
+                _ => log::warn!("Ignoring invalid STATUS_LABELS entry {}", pair),
+            }
+        }
+        labels
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            strict_fields: false,
+            sanitize_titles: false,
+            app_base_url: "http://127.0.0.1:8080".to_string(),
+            snapshot_path: "snapshot.json".into(),
+            snapshot_compress: false,
+            snapshot_interval_secs: 0,
+            max_bulk_size: 1000,
+            undo_window_secs: 0,
+            status_labels: StatusLabels::default(),
+        }
+    }
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let defaults = Config::default();
+        Config {
+            strict_fields: env_flag("STRICT_FIELDS"),
+            sanitize_titles: env_flag("SANITIZE_TITLES"),
+            app_base_url: std::env::var("APP_BASE_URL").unwrap_or(defaults.app_base_url),
+            snapshot_path: std::env::var("SNAPSHOT_PATH")
+                .map(PathBuf::from)
+                .unwrap_or(defaults.snapshot_path),
+            snapshot_compress: env_flag("SNAPSHOT_COMPRESS"),
+            snapshot_interval_secs: env_parse("SNAPSHOT_INTERVAL_SECS", defaults.snapshot_interval_secs),
+            max_bulk_size: env_parse("MAX_BULK_SIZE", defaults.max_bulk_size),
+            undo_window_secs: env_parse("UNDO_WINDOW_SECS", defaults.undo_window_secs),
+            status_labels: std::env::var("STATUS_LABELS")
+                .map(|v| StatusLabels::parse(&v))
+                .unwrap_or(defaults.status_labels),
+        }
+    }
+}
+
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|v| matches!(v.to_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
 struct AppState {
     todos: Mutex<Vec<Todo>>,
+    config: Config,
+    // Id of the todo currently being focused on, if any.
+    focus: Mutex<Option<String>>,
+    lock_stats: LockStats,
+    // Append-only record of completed todos converted with archive-as-note.
+    done_log: Mutex<Vec<DoneLogEntry>>,
+    import_jobs: Mutex<ImportJobs>,
+    // Recently deleted todos that can still be restored, oldest first.
+    tombstones: Mutex<Vec<Tombstone>>,
+}
+
+// Wall-clock time rather than Instant, so the undo window survives a restart
+// when tombstones are restored from a snapshot.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Tombstone {
+    todo: Todo,
+    deleted_at: SystemTime,
+}
+
+// Progress of async imports. Running jobs are always kept; finished ones are
+// evicted oldest-first past MAX_FINISHED_IMPORT_JOBS.
+#[derive(Debug, Default)]
+struct ImportJobs {
+    jobs: HashMap<String, ImportJob>,
+    finished: VecDeque<String>,
+}
+
+impl ImportJobs {
+    fn finish(&mut self, job_id: &str) {
+        let Some(job) = self.jobs.get_mut(job_id) else {
+            return;
+        };
+        job.status = ImportStatus::Completed;
+        self.finished.push_back(job_id.to_string());
+        while self.finished.len() > MAX_FINISHED_IMPORT_JOBS {
+            if let Some(oldest) = self.finished.pop_front() {
+                self.jobs.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ImportStatus {
+    Running,
+    Completed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ImportJob {
+    status: ImportStatus,
+    total: usize,
+    processed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct DoneLogEntry {
+    todo_id: String,
+    title: String,
+}
+
+impl AppState {
+    fn new(todos: Vec<Todo>, config: Config) -> Self {
+        AppState {
+            todos: Mutex::new(todos),
+            config,
+            focus: Mutex::new(None),
+            lock_stats: LockStats::default(),
+            done_log: Mutex::new(Vec::new()),
+            import_jobs: Mutex::new(ImportJobs::default()),
+            tombstones: Mutex::new(Vec::new()),
+        }
+    }
+
+    // Rebuilds the store from a snapshot written by `snapshot()`.
+    fn from_snapshot(snapshot: Snapshot, config: Config) -> Self {
+        AppState {
+            done_log: Mutex::new(snapshot.done_log),
+            tombstones: Mutex::new(snapshot.tombstones),
+            ..AppState::new(snapshot.todos, config)
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let todos = self.lock_todos().clone();
+        let done_log = self.done_log.lock().unwrap().clone();
+        let tombstones = self.tombstones.lock().unwrap().clone();
+        Snapshot { todos, done_log, tombstones }
+    }
+
+    fn undo_window(&self) -> Duration {
+        Duration::from_secs(self.config.undo_window_secs)
+    }
+
+    // Hard-deletes tombstones whose undo window has passed as of `now`,
+    // returning how many were dropped.
+    fn purge_tombstones(&self, now: SystemTime) -> usize {
+        let window = self.undo_window();
+        let mut tombstones = self.tombstones.lock().unwrap();
+        let before = tombstones.len();
+        tombstones.retain(|t| now.duration_since(t.deleted_at).unwrap_or_default() < window);
+        before - tombstones.len()
+    }
+
+    // Locks the store, recording how long the caller waited for it.
+    fn lock_todos(&self) -> MutexGuard<'_, Vec<Todo>> {
+        let started = Instant::now();
+        let guard = self.todos.lock().unwrap();
+        self.lock_stats.record(started.elapsed().as_nanos() as u64);
+        guard
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockStats {
+    acquisitions: AtomicU64,
+    total_wait_ns: AtomicU64,
+    max_wait_ns: AtomicU64,
+}
+
+impl LockStats {
+    fn record(&self, wait_ns: u64) {
+        self.acquisitions.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_ns.fetch_add(wait_ns, Ordering::Relaxed);
+        self.max_wait_ns.fetch_max(wait_ns, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockStatsResponse {
+    acquisitions: u64,
+    total_wait_ns: u64,
+    max_wait_ns: u64,
+    mean_wait_ns: u64,
+}
+
+// Rejects bulk requests over MAX_BULK_SIZE so one request can't hold the store lock for long.
+fn check_bulk_size(config: &Config, len: usize) -> Result<(), HttpResponse> {
+    check_size_limit(config.max_bulk_size, len)
+}
+
+fn check_size_limit(limit: usize, len: usize) -> Result<(), HttpResponse> {
+    if len > limit {
+        return Err(HttpResponse::BadRequest().json(format!(
+            "Bulk requests are limited to {} items, got {}",
+            limit, len
+        )));
+    }
+
+    Ok(())
+}
+
+// Deserializes a request body as `T`, or through its strict counterpart `S`
+// when STRICT_FIELDS is enabled so unknown fields are reported instead of ignored.
+fn parse_body<T, S>(config: &Config, body: serde_json::Value) -> Result<T, String>
+where
+    T: DeserializeOwned,
+    S: DeserializeOwned + Into<T>,
+{
+    let parsed = if config.strict_fields {
+        serde_json::from_value::<S>(body).map(Into::into)
+    } else {
+        serde_json::from_value::<T>(body)
+    };
+
+    parsed.map_err(|e| format!("Invalid request body: {}", e))
+}
+
+// All create-time checks, shared by create_todo and the validate endpoint.
+// Returns the title to store, or every problem found with the payload.
+fn validate_create(config: &Config, body: serde_json::Value) -> Result<String, Vec<String>> {
+    let todo_req: CreateTodoRequest =
+        parse_body::<_, StrictCreateTodoRequest>(config, body).map_err(|e| vec![e])?;
+
+    validate_title(config, todo_req.title)
+}
+
+// Title rules shared by create and update. Runs after sanitization, so a title
+// that sanitizes down to nothing is rejected too.
+fn validate_title(config: &Config, title: String) -> Result<String, Vec<String>> {
+    let title = prepare_title(config, title);
+    let mut errors = Vec::new();
+    if title.trim().is_empty() {
+        errors.push("Title must not be empty".to_string());
+    }
+
+    if errors.is_empty() { Ok(title) } else { Err(errors) }
+}
+
+fn schema_version_header() -> DefaultHeaders {
+    DefaultHeaders::new().add(("X-Schema-Version", SCHEMA_VERSION))
+}
+
+// Whether the client opted into enveloped responses, via `?envelope=true` or an
+// `envelope=true` parameter on its Accept header.
+fn wants_envelope(req: &ServiceRequest) -> bool {
+    let query = req.query_string().split('&').any(|pair| pair == "envelope=true");
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(';').any(|param| param.trim() == "envelope=true"));
+
+    query || accept
+}
+
+// Wraps JSON responses as `{ "api_version": ..., "data": ... }` for clients that
+// asked for it; everyone else gets the bare body.
+async fn envelope(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let enveloped = wants_envelope(&req);
+    let res = next.call(req).await?;
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !enveloped || !is_json {
+        return Ok(res.map_into_boxed_body());
+    }
+
+    let (req, res) = res.into_parts();
+    let (res, body) = res.into_parts();
+    let bytes = body::to_bytes(body)
+        .await
+        .map_err(|_| actix_web::error::ErrorInternalServerError("Failed to read response body"))?;
+    let data: serde_json::Value = if bytes.is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_slice(&bytes)?
+    };
+    let wrapped = serde_json::json!({ "api_version": SCHEMA_VERSION, "data": data });
+    let res = res.set_body(serde_json::to_vec(&wrapped)?).map_into_boxed_body();
+
+    Ok(ServiceResponse::new(req, res))
+}
+
+// Removes characters that render invisibly or break displays (control codes,
+// zero-width spaces, word joiners, BOMs) and collapses runs of whitespace into
+// one space. ZWJ/ZWNJ are kept: they shape emoji sequences and scripts like Persian.
+fn sanitize_title(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .filter_map(|c| match c {
+            '\u{200B}' | '\u{2060}' | '\u{FEFF}' => None,
+            c if c.is_whitespace() => Some(' '),
+            c if c.is_control() => None,
+            c => Some(c),
+        })
+        .collect();
+
+    cleaned.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn prepare_title(config: &Config, title: String) -> String {
+    if config.sanitize_titles {
+        sanitize_title(&title)
+    } else {
+        title
+    }
 }
 
 #[get("")]
 async fn get_todos(app_state: web::Data<Arc<AppState>>) -> impl Responder {
-    let todos = app_state.todos.lock().unwrap();
+    let todos = app_state.lock_todos();
 
     HttpResponse::Ok().json(todos.clone())
 }
 
+#[derive(Debug, Deserialize)]
+struct TitleQuery {
+    title: String,
+}
+
+// Case-insensitive, whitespace-insensitive form of a title used for lookups.
+fn normalize_title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// Returns every todo whose normalized title matches. Titles aren't unique, so
+// the result is always an array; 404 means nothing matched.
+#[get("/by-title")]
+async fn get_todos_by_title(
+    app_state: web::Data<Arc<AppState>>,
+    query: web::Query<TitleQuery>,
+) -> impl Responder {
+    let wanted = normalize_title(&query.title);
+    let todos = app_state.lock_todos();
+    let matches: Vec<Todo> = todos
+        .iter()
+        .filter(|t| normalize_title(&t.title) == wanted)
+        .cloned()
+        .collect();
+
+    if matches.is_empty() {
+        HttpResponse::NotFound().json(format!("No todo titled {}", query.title))
+    } else {
+        HttpResponse::Ok().json(matches)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SelectQuery {
+    // e.g. `id,title` to return only those fields.
+    select: Option<String>,
+}
+
 #[get("/{id}")]
-async fn get_todo(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+async fn get_todo(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<String>,
+    query: web::Query<SelectQuery>,
+) -> impl Responder {
     let todo_id = path.into_inner();
-    let todos = app_state.todos.lock().unwrap();
+    let todos = app_state.lock_todos();
 
     if let Some(todo) = todos.iter().find(|t| t.id == todo_id) {
-        HttpResponse::Ok().json(todo)
+        let Some(selection) = &query.select else {
+            return HttpResponse::Ok().json(todo);
+        };
+        let value = serde_json::to_value(todo).unwrap();
+        match select::parse(selection).and_then(|fields| select::apply(&fields, &value)) {
+            Ok(selected) => HttpResponse::Ok().json(selected),
+            Err(message) => HttpResponse::BadRequest().json(message),
+        }
     } else {
         HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id))
     }
@@ -48,15 +532,48 @@ async fn get_todo(app_state: web::Data<Arc<AppState>>, path: web::Path<String>)
 #[post("")]
 async fn create_todo(
     app_state: web::Data<Arc<AppState>>,
-    todo_req: web::Json<CreateTodoRequest>,
+    body: web::Json<serde_json::Value>,
+) -> impl Responder {
+    let title = match validate_create(&app_state.config, body.into_inner()) {
+        Ok(title) => title,
+        Err(errors) => return HttpResponse::BadRequest().json(errors.join("; ")),
+    };
+    let new_todo = Todo::new(title);
+
+    let mut todos = app_state.lock_todos();
+    todos.push(new_todo.clone());
+
+    HttpResponse::Created().json(new_todo)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ValidationResponse {
+    valid: bool,
+    errors: Vec<String>,
+}
+
+// Runs create_todo's validation without creating anything.
+#[post("/validate")]
+async fn validate_todo(
+    app_state: web::Data<Arc<AppState>>,
+    body: web::Json<serde_json::Value>,
 ) -> impl Responder {
-    let new_todo = Todo {
-        id: Uuid::new_v4().to_string(),
-        title: todo_req.title.clone(),
-        completed: false,
+    match validate_create(&app_state.config, body.into_inner()) {
+        Ok(_) => HttpResponse::Ok().json(ValidationResponse { valid: true, errors: Vec::new() }),
+        Err(errors) => HttpResponse::BadRequest().json(ValidationResponse { valid: false, errors }),
+    }
+}
+
+// Quick capture for scripts and shortcuts: the raw text body becomes the title.
+#[post("/inbox")]
+async fn inbox(app_state: web::Data<Arc<AppState>>, body: String) -> impl Responder {
+    let title = match validate_title(&app_state.config, body.trim().to_string()) {
+        Ok(title) => title,
+        Err(errors) => return HttpResponse::BadRequest().json(errors.join("; ")),
     };
+    let new_todo = Todo::new(title);
 
-    let mut todos = app_state.todos.lock().unwrap();
+    let mut todos = app_state.lock_todos();
     todos.push(new_todo.clone());
 
     HttpResponse::Created().json(new_todo)
@@ -66,12 +583,20 @@ async fn create_todo(
 async fn update_todo(
     app_state: web::Data<Arc<AppState>>,
     path: web::Path<String>,
-    todo_req: web::Json<UpdateTodoRequest>,
+    body: web::Json<serde_json::Value>,
 ) -> impl Responder {
+    let todo_req: UpdateTodoRequest = match parse_body::<_, StrictUpdateTodoRequest>(&app_state.config, body.into_inner()) {
+        Ok(req) => req,
+        Err(message) => return HttpResponse::BadRequest().json(message),
+    };
+    let title = match validate_title(&app_state.config, todo_req.title) {
+        Ok(title) => title,
+        Err(errors) => return HttpResponse::BadRequest().json(errors.join("; ")),
+    };
     let todo_id = path.into_inner();
-    let mut todos = app_state.todos.lock().unwrap();
+    let mut todos = app_state.lock_todos();
     if let Some(todo_index) = todos.iter().position(|t| t.id == todo_id) {
-        todos[todo_index].title = todo_req.title.clone();
+        todos[todo_index].title = title;
 
         HttpResponse::Ok().json(todos[todo_index].clone())
     } else {
@@ -79,104 +604,792 @@ async fn update_todo(
     }
 }
 
+// Removes a todo along with any references to it (related links, focus).
+fn remove_todo(app_state: &AppState, todos: &mut Vec<Todo>, todo_id: &str) -> Option<Todo> {
+    let todo_index = todos.iter().position(|t| t.id == todo_id)?;
+    let removed = todos.remove(todo_index);
+    for todo in todos.iter_mut() {
+        todo.related.retain(|id| id != todo_id);
+    }
+    let mut focus = app_state.focus.lock().unwrap();
+    if focus.as_deref() == Some(todo_id) {
+        *focus = None;
+    }
+
+    Some(removed)
+}
+
+// Deletes a todo on a client's behalf, keeping a tombstone for restore when
+// UNDO_WINDOW_SECS is set.
+fn delete_with_undo(app_state: &AppState, todos: &mut Vec<Todo>, todo_id: &str) -> Option<Todo> {
+    let deleted = remove_todo(app_state, todos, todo_id)?;
+    if app_state.config.undo_window_secs > 0 {
+        app_state.tombstones.lock().unwrap().push(Tombstone {
+            todo: deleted.clone(),
+            deleted_at: SystemTime::now(),
+        });
+    }
+
+    Some(deleted)
+}
+
+#[post("/{id}/restore")]
+async fn restore_todo(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+    let todo_id = path.into_inner();
+    app_state.purge_tombstones(SystemTime::now());
+
+    let mut todos = app_state.lock_todos();
+    let mut tombstones = app_state.tombstones.lock().unwrap();
+    let Some(index) = tombstones.iter().rposition(|t| t.todo.id == todo_id) else {
+        return HttpResponse::NotFound().json(format!("No deleted todo with id {} to restore", todo_id));
+    };
+    let mut todo = tombstones.remove(index).todo;
+
+    // Links were dropped from the other side on delete; re-link whatever still exists.
+    todo.related.retain(|id| todos.iter().any(|t| t.id == *id));
+    for other in todos.iter_mut().filter(|t| todo.related.contains(&t.id)) {
+        if !other.related.contains(&todo.id) {
+            other.related.push(todo.id.clone());
+        }
+    }
+    todos.push(todo.clone());
+
+    HttpResponse::Ok().json(todo)
+}
+
+fn spawn_tombstone_purge(app_state: Arc<AppState>) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(app_state.undo_window());
+        loop {
+            ticker.tick().await;
+            let purged = app_state.purge_tombstones(SystemTime::now());
+            if purged > 0 {
+                log::debug!("Purged {} expired tombstones", purged);
+            }
+        }
+    });
+}
+
+#[derive(Debug, Deserialize)]
+struct DeleteQuery {
+    // `representation` returns the deleted todo instead of an empty 204.
+    #[serde(rename = "return")]
+    return_: Option<String>,
+}
+
+// Whether the client asked for the deleted todo back, via `?return=representation`
+// or a `Prefer: return=representation` header.
+fn wants_representation(req: &HttpRequest, query: &DeleteQuery) -> bool {
+    let prefer = req
+        .headers()
+        .get_all("Prefer")
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| v.trim().eq_ignore_ascii_case("return=representation"));
+
+    prefer || query.return_.as_deref() == Some("representation")
+}
+
 #[delete("/{id}")]
 async fn delete_todo(
     app_state: web::Data<Arc<AppState>>,
     path: web::Path<String>,
+    query: web::Query<DeleteQuery>,
+    req: HttpRequest,
 ) -> impl Responder {
     let todo_id = path.into_inner();
-    let mut todos = app_state.todos.lock().unwrap();
-    if let Some(todo_index) = todos.iter().position(|t| t.id == todo_id) {
-        todos.remove(todo_index);
+    let mut todos = app_state.lock_todos();
+    if let Some(deleted) = delete_with_undo(&app_state, &mut todos, &todo_id) {
+        if wants_representation(&req, &query) {
+            HttpResponse::Ok().json(deleted)
+        } else {
+            HttpResponse::NoContent().json("")
+        }
+    } else {
+        HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id))
+    }
+}
 
-        HttpResponse::NoContent().json("")
+#[derive(Debug, Serialize, Deserialize)]
+struct TodoLink {
+    id: String,
+    url: String,
+}
+
+#[get("/{id}/link")]
+async fn get_todo_link(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+    let todo_id = path.into_inner();
+    let todos = app_state.lock_todos();
+
+    if todos.iter().any(|t| t.id == todo_id) {
+        let base = app_state.config.app_base_url.trim_end_matches('/');
+        HttpResponse::Ok().json(TodoLink {
+            url: format!("{}/todos/{}", base, todo_id),
+            id: todo_id,
+        })
     } else {
         HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id))
     }
 }
 
-#[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    dotenv().expect(".env file not found");
-    env_logger::init();
+#[derive(Debug, Serialize, Deserialize)]
+struct BulkIdsRequest {
+    ids: Vec<String>,
+}
 
-    let app_state = Arc::new(AppState {
-        todos: Mutex::new(Vec::new()),
-    });
+#[derive(Debug, Serialize, Deserialize)]
+struct BulkCountResponse {
+    count: usize,
+}
 
-    HttpServer::new(move || {
-        App::new()
-            .app_data(web::Data::new(app_state.clone()))
-            .service(
-                web::scope("/todos")
-                    .service(get_todos)
-                    .service(get_todo)
-                    .service(create_todo)
-                    .service(update_todo)
-                    .service(delete_todo)
-            )
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchGetResponse {
+    // In the order the ids were requested.
+    todos: Vec<Todo>,
+    not_found: Vec<String>,
 }
 
-#[cfg(test)]
-mod tests {
-    use actix_web::http::StatusCode;
-    use actix_web::test;
-    use super::*;
+#[post("/batch-get")]
+async fn batch_get(
+    app_state: web::Data<Arc<AppState>>,
+    req: web::Json<BulkIdsRequest>,
+) -> impl Responder {
+    if let Err(resp) = check_bulk_size(&app_state.config, req.ids.len()) {
+        return resp;
+    }
+    let todos = app_state.lock_todos();
+    let mut found = Vec::new();
+    let mut not_found = Vec::new();
+    for id in &req.ids {
+        match todos.iter().find(|t| t.id == *id) {
+            Some(todo) => found.push(todo.clone()),
+            None => not_found.push(id.clone()),
+        }
+    }
 
-    #[actix_web::test]
-    async fn test_get_empty_todos() {
-        let app_state = Arc::new(AppState {
-            todos: Mutex::new(Vec::new()),
-        });
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(app_state.clone()))
-                .service(web::scope("/todos").service(get_todos))
-        ).await;
-        let req = test::TestRequest::get().uri("/todos").to_request();
-        let result = test::call_service(&app, req).await;
-        assert_eq!(result.status(), StatusCode::OK);
+    HttpResponse::Ok().json(BatchGetResponse { todos: found, not_found })
+}
 
-        let expected: Vec<Todo> = Vec::new();
-        let expected_json = serde_json::to_string(&expected).unwrap();
-        let actual_json = test::read_body(result).await;
-        assert_eq!(actual_json, expected_json);
+#[post("/bulk-uncomplete")]
+async fn bulk_uncomplete(
+    app_state: web::Data<Arc<AppState>>,
+    req: web::Json<BulkIdsRequest>,
+) -> impl Responder {
+    if let Err(resp) = check_bulk_size(&app_state.config, req.ids.len()) {
+        return resp;
+    }
+    let mut todos = app_state.lock_todos();
+    let mut count = 0;
+    for todo in todos.iter_mut().filter(|t| req.ids.contains(&t.id)) {
+        todo.completed = false;
+        count += 1;
     }
 
-    #[actix_web::test]
-    async fn test_get_todos() {
-        let mut v = Vec::new();
-        v.push(Todo {
-            id: Uuid::new_v4().to_string(),
-            title: "title".to_string(),
-            completed: false,
-        });
-        let app_state = Arc::new(AppState {
-            todos: Mutex::new(v),
-        });
-        let app = test::init_service(
-            App::new()
-                .app_data(web::Data::new(app_state.clone()))
-                .service(web::scope("/todos").service(get_todos))
-        ).await;
-        let req = test::TestRequest::get().uri("/todos").to_request();
+    HttpResponse::Ok().json(BulkCountResponse { count })
+}
 
-        let resp = test::call_service(&app, req).await;
-        assert_eq!(resp.status(), StatusCode::OK);
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchLineResult {
+    line: usize,
+    ok: bool,
+    // The todo the command acted on, when it succeeded.
+    id: Option<String>,
+    error: Option<String>,
+}
 
-        let todos: Vec<Todo> = test::read_body_json(resp).await;
-        assert_eq!(todos.len(), 1);
+// Runs one `batch-text` command against the store, returning the affected todo's id.
+fn run_batch_command(app_state: &AppState, todos: &mut Vec<Todo>, line: &str) -> Result<String, String> {
+    let (command, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let arg = arg.trim();
+    match command {
+        "create" => {
+            let title = validate_title(&app_state.config, arg.to_string()).map_err(|errors| errors.join("; "))?;
+            let todo = Todo::new(title);
+            let id = todo.id.clone();
+            todos.push(todo);
+            Ok(id)
+        }
+        "complete" => match todos.iter_mut().find(|t| t.id == arg) {
+            Some(todo) => {
+                todo.completed = true;
+                Ok(todo.id.clone())
+            }
+            None => Err(format!("Todo with id {} not found", arg)),
+        },
+        "delete" => match delete_with_undo(app_state, todos, arg) {
+            Some(todo) => Ok(todo.id),
+            None => Err(format!("Todo with id {} not found", arg)),
+        },
+        _ => Err(format!("Unknown command {}", command)),
     }
+}
 
-    #[actix_web::test]
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct BulkFilter {
+    completed: Option<bool>,
+}
+
+impl BulkFilter {
+    fn is_empty(&self) -> bool {
+        self.completed.is_none()
+    }
+
+    fn matches(&self, todo: &Todo) -> bool {
+        self.completed.is_none_or(|completed| todo.completed == completed)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum BulkAction {
+    Delete,
+    Complete,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BulkFilterRequest {
+    #[serde(rename = "where")]
+    filter: BulkFilter,
+    action: BulkAction,
+}
+
+#[derive(Debug, Deserialize)]
+struct ConfirmQuery {
+    #[serde(default)]
+    confirm: bool,
+}
+
+// Applies an action to every todo matching a filter. An empty filter matches
+// everything, so it needs `?confirm=true` to guard against accidents.
+#[post("/bulk")]
+async fn bulk_by_filter(
+    app_state: web::Data<Arc<AppState>>,
+    req: web::Json<BulkFilterRequest>,
+    query: web::Query<ConfirmQuery>,
+) -> impl Responder {
+    if req.filter.is_empty() && !query.confirm {
+        return HttpResponse::BadRequest().json("An empty filter matches every todo; pass ?confirm=true to proceed");
+    }
+
+    let mut todos = app_state.lock_todos();
+    let matched: Vec<String> = todos
+        .iter()
+        .filter(|t| req.filter.matches(t))
+        .map(|t| t.id.clone())
+        .collect();
+    for id in &matched {
+        match req.action {
+            BulkAction::Delete => {
+                delete_with_undo(&app_state, &mut todos, id);
+            }
+            BulkAction::Complete => {
+                if let Some(todo) = todos.iter_mut().find(|t| t.id == *id) {
+                    todo.completed = true;
+                }
+            }
+        }
+    }
+
+    HttpResponse::Ok().json(BulkCountResponse { count: matched.len() })
+}
+
+// Executes a plain-text script with one command per line (`create <title>`,
+// `complete <id>`, `delete <id>`), in order. Blank lines are skipped.
+#[post("/batch-text")]
+async fn batch_text(app_state: web::Data<Arc<AppState>>, body: String) -> impl Responder {
+    let commands = body.lines().filter(|line| !line.trim().is_empty()).count();
+    if let Err(resp) = check_bulk_size(&app_state.config, commands) {
+        return resp;
+    }
+    let mut todos = app_state.lock_todos();
+    let results: Vec<BatchLineResult> = body
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| match run_batch_command(&app_state, &mut todos, line.trim()) {
+            Ok(id) => BatchLineResult { line: index + 1, ok: true, id: Some(id), error: None },
+            Err(error) => BatchLineResult { line: index + 1, ok: false, id: None, error: Some(error) },
+        })
+        .collect();
+
+    HttpResponse::Ok().json(results)
+}
+
+#[post("/{id}/link/{other_id}")]
+async fn link_todo(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (todo_id, other_id) = path.into_inner();
+    if todo_id == other_id {
+        return HttpResponse::BadRequest().json("A todo cannot be linked to itself");
+    }
+
+    let mut todos = app_state.lock_todos();
+    let Some(todo_index) = todos.iter().position(|t| t.id == todo_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id));
+    };
+    let Some(other_index) = todos.iter().position(|t| t.id == other_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", other_id));
+    };
+
+    if !todos[todo_index].related.contains(&other_id) {
+        todos[todo_index].related.push(other_id);
+    }
+    if !todos[other_index].related.contains(&todo_id) {
+        todos[other_index].related.push(todo_id);
+    }
+
+    HttpResponse::Ok().json(todos[todo_index].clone())
+}
+
+#[delete("/{id}/link/{other_id}")]
+async fn unlink_todo(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<(String, String)>,
+) -> impl Responder {
+    let (todo_id, other_id) = path.into_inner();
+    let mut todos = app_state.lock_todos();
+    let Some(todo_index) = todos.iter().position(|t| t.id == todo_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id));
+    };
+    let Some(other_index) = todos.iter().position(|t| t.id == other_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", other_id));
+    };
+
+    todos[todo_index].related.retain(|id| *id != other_id);
+    todos[other_index].related.retain(|id| *id != todo_id);
+
+    HttpResponse::Ok().json(todos[todo_index].clone())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportItem {
+    title: String,
+    #[serde(default)]
+    completed: bool,
+}
+
+impl From<ImportItem> for Todo {
+    fn from(item: ImportItem) -> Self {
+        let mut todo = Todo::new(item.title);
+        todo.completed = item.completed;
+        todo
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ImportQuery {
+    #[serde(default, rename = "async")]
+    async_: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImportJobStarted {
+    job_id: String,
+}
+
+// Inserts todos a chunk at a time, releasing the lock in between so other
+// requests aren't blocked for the length of a large import.
+async fn run_import_job(app_state: Arc<AppState>, job_id: String, todos: Vec<Todo>) {
+    let mut todos = todos.into_iter().peekable();
+    while todos.peek().is_some() {
+        let chunk: Vec<Todo> = todos.by_ref().take(IMPORT_CHUNK_SIZE).collect();
+        let chunk_len = chunk.len();
+        app_state.lock_todos().extend(chunk);
+        if let Some(job) = app_state.import_jobs.lock().unwrap().jobs.get_mut(&job_id) {
+            job.processed += chunk_len;
+        }
+        actix_web::rt::task::yield_now().await;
+    }
+
+    app_state.import_jobs.lock().unwrap().finish(&job_id);
+}
+
+// Imports a JSON array of todos. With `?async=true` the import runs in the
+// background with a higher size limit (ASYNC_IMPORT_BULK_MULTIPLIER times
+// MAX_BULK_SIZE), and its progress is polled through the returned job id.
+#[post("/import")]
+async fn import_items(
+    app_state: web::Data<Arc<AppState>>,
+    items: web::Json<Vec<ImportItem>>,
+    query: web::Query<ImportQuery>,
+) -> impl Responder {
+    let mut new_todos = Vec::with_capacity(items.len());
+    for (index, item) in items.into_inner().into_iter().enumerate() {
+        let mut todo = Todo::from(item);
+        todo.title = match validate_title(&app_state.config, todo.title) {
+            Ok(title) => title,
+            Err(errors) => return HttpResponse::BadRequest().json(format!("[{}].title: {}", index, errors.join("; "))),
+        };
+        new_todos.push(todo);
+    }
+
+    if !query.async_ {
+        if let Err(resp) = check_bulk_size(&app_state.config, new_todos.len()) {
+            return resp;
+        }
+        app_state.lock_todos().extend(new_todos.iter().cloned());
+        return HttpResponse::Created().json(new_todos);
+    }
+
+    let async_limit = app_state.config.max_bulk_size.saturating_mul(ASYNC_IMPORT_BULK_MULTIPLIER);
+    if let Err(resp) = check_size_limit(async_limit, new_todos.len()) {
+        return resp;
+    }
+
+    let job_id = Uuid::new_v4().to_string();
+    app_state.import_jobs.lock().unwrap().jobs.insert(
+        job_id.clone(),
+        ImportJob {
+            status: ImportStatus::Running,
+            total: new_todos.len(),
+            processed: 0,
+        },
+    );
+    actix_web::rt::spawn(run_import_job(app_state.get_ref().clone(), job_id.clone(), new_todos));
+
+    HttpResponse::Accepted().json(ImportJobStarted { job_id })
+}
+
+#[get("/import/{job_id}/status")]
+async fn get_import_status(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+    let job_id = path.into_inner();
+    let import_jobs = app_state.import_jobs.lock().unwrap();
+
+    match import_jobs.jobs.get(&job_id) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => HttpResponse::NotFound().json(format!("Import job with id {} not found", job_id)),
+    }
+}
+
+#[post("/import/csv")]
+async fn import_csv(app_state: web::Data<Arc<AppState>>, body: web::Bytes) -> impl Responder {
+    let rows = match import::parse_csv(&app_state.config, &body) {
+        Ok(rows) => rows,
+        Err(message) => return HttpResponse::BadRequest().json(message),
+    };
+    if let Err(resp) = check_bulk_size(&app_state.config, rows.len()) {
+        return resp;
+    }
+
+    let mut todos = app_state.lock_todos();
+    // Ids only select a todo to update; new todos always get a fresh id, so
+    // validate every row before touching the store.
+    let mut targets = Vec::with_capacity(rows.len());
+    for (index, row) in rows.iter().enumerate() {
+        let existing = match &row.id {
+            Some(id) => match todos.iter().position(|t| t.id == *id) {
+                Some(position) => Some(position),
+                None => {
+                    return HttpResponse::BadRequest()
+                        .json(format!("Row {}: todo with id {} not found", index + 1, id));
+                }
+            },
+            None => None,
+        };
+        targets.push(existing);
+    }
+
+    let mut imported = Vec::with_capacity(rows.len());
+    for (row, existing) in rows.into_iter().zip(targets) {
+        let todo = match existing {
+            Some(index) => &mut todos[index],
+            None => {
+                todos.push(Todo::new(String::new()));
+                todos.last_mut().unwrap()
+            }
+        };
+        todo.title = row.title;
+        if let Some(completed) = row.completed {
+            todo.completed = completed;
+        }
+        imported.push(todo.clone());
+    }
+
+    HttpResponse::Created().json(imported)
+}
+
+#[post("/import/{format}")]
+async fn import_todos(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<String>,
+    body: web::Json<serde_json::Value>,
+) -> impl Responder {
+    let format = path.into_inner();
+    let result = match import::parse(&app_state.config, &format, &body) {
+        Ok(result) => result,
+        Err(message) => return HttpResponse::BadRequest().json(message),
+    };
+    if let Err(resp) = check_bulk_size(&app_state.config, result.imported.len()) {
+        return resp;
+    }
+
+    let mut todos = app_state.lock_todos();
+    todos.extend(result.imported.iter().cloned());
+
+    HttpResponse::Created().json(result)
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ReopenRequest {
+    reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StrictReopenRequest {
+    reason: Option<String>,
+}
+
+impl From<StrictReopenRequest> for ReopenRequest {
+    fn from(req: StrictReopenRequest) -> Self {
+        ReopenRequest { reason: req.reason }
+    }
+}
+
+// The body is optional: an empty one means no reason, but anything else must
+// parse, so a malformed reason is reported rather than silently dropped.
+#[post("/{id}/reopen")]
+async fn reopen_todo(
+    app_state: web::Data<Arc<AppState>>,
+    path: web::Path<String>,
+    body: web::Bytes,
+) -> impl Responder {
+    let reason = if body.is_empty() {
+        None
+    } else {
+        let parsed = serde_json::from_slice(&body)
+            .map_err(|e| format!("Invalid request body: {}", e))
+            .and_then(|value| parse_body::<ReopenRequest, StrictReopenRequest>(&app_state.config, value));
+        match parsed {
+            Ok(req) => req.reason,
+            Err(message) => return HttpResponse::BadRequest().json(message),
+        }
+    };
+    let todo_id = path.into_inner();
+    let mut todos = app_state.lock_todos();
+    let Some(todo) = todos.iter_mut().find(|t| t.id == todo_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id));
+    };
+    if !todo.completed {
+        return HttpResponse::Conflict().json(format!("Todo with id {} is not completed", todo_id));
+    }
+
+    todo.completed = false;
+    match reason.as_deref().map(str::trim).filter(|r| !r.is_empty()) {
+        Some(reason) => todo.history.push(format!("Reopened: {}", reason)),
+        None => todo.history.push("Reopened".to_string()),
+    }
+
+    HttpResponse::Ok().json(todo.clone())
+}
+
+// Moves a completed todo out of the active list and into the done log.
+#[post("/{id}/archive-as-note")]
+async fn archive_as_note(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+    let todo_id = path.into_inner();
+    let mut todos = app_state.lock_todos();
+    let Some(todo) = todos.iter().find(|t| t.id == todo_id) else {
+        return HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id));
+    };
+    if !todo.completed {
+        return HttpResponse::Conflict().json(format!("Todo with id {} is not completed", todo_id));
+    }
+
+    let todo = remove_todo(&app_state, &mut todos, &todo_id).unwrap();
+    let entry = DoneLogEntry {
+        todo_id: todo.id,
+        title: todo.title,
+    };
+    app_state.done_log.lock().unwrap().push(entry.clone());
+
+    HttpResponse::Created().json(entry)
+}
+
+#[get("/done-log")]
+async fn get_done_log(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+    let done_log = app_state.done_log.lock().unwrap();
+
+    HttpResponse::Ok().json(done_log.clone())
+}
+
+#[get("/focus")]
+async fn get_focus(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+    let todos = app_state.lock_todos();
+    let focus = app_state.focus.lock().unwrap();
+
+    match focus.as_ref().and_then(|id| todos.iter().find(|t| t.id == *id)) {
+        Some(todo) => HttpResponse::Ok().json(todo),
+        None => HttpResponse::NotFound().json("No todo is in focus"),
+    }
+}
+
+#[post("/{id}/focus")]
+async fn set_focus(app_state: web::Data<Arc<AppState>>, path: web::Path<String>) -> impl Responder {
+    let todo_id = path.into_inner();
+    let todos = app_state.lock_todos();
+
+    if let Some(todo) = todos.iter().find(|t| t.id == todo_id) {
+        *app_state.focus.lock().unwrap() = Some(todo_id);
+
+        HttpResponse::Ok().json(todo)
+    } else {
+        HttpResponse::NotFound().json(format!("Todo with id {} not found", todo_id))
+    }
+}
+
+#[delete("/focus")]
+async fn clear_focus(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+    *app_state.focus.lock().unwrap() = None;
+
+    HttpResponse::NoContent().finish()
+}
+
+#[get("/labels")]
+async fn get_labels(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+    HttpResponse::Ok().json(&app_state.config.status_labels)
+}
+
+#[get("/stats/locks")]
+async fn get_lock_stats(app_state: web::Data<Arc<AppState>>) -> impl Responder {
+    let stats = &app_state.lock_stats;
+    let acquisitions = stats.acquisitions.load(Ordering::Relaxed);
+    let total_wait_ns = stats.total_wait_ns.load(Ordering::Relaxed);
+
+    HttpResponse::Ok().json(LockStatsResponse {
+        acquisitions,
+        total_wait_ns,
+        max_wait_ns: stats.max_wait_ns.load(Ordering::Relaxed),
+        mean_wait_ns: total_wait_ns.checked_div(acquisitions).unwrap_or(0),
+    })
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    dotenv().expect(".env file not found");
+    env_logger::init();
+
+    let config = Config::from_env();
+    let snapshots_enabled = config.snapshot_interval_secs > 0;
+    let snapshot = if snapshots_enabled {
+        snapshot::load(&config.snapshot_path)?
+    } else {
+        Snapshot::default()
+    };
+    let app_state = Arc::new(AppState::from_snapshot(snapshot, config));
+
+    if snapshots_enabled {
+        snapshot::spawn(
+            app_state.clone(),
+            Duration::from_secs(app_state.config.snapshot_interval_secs),
+        );
+    }
+
+    if app_state.config.undo_window_secs > 0 {
+        spawn_tombstone_purge(app_state.clone());
+    }
+
+    let server_state = app_state.clone();
+    HttpServer::new(move || {
+        App::new()
+            // Trailing slashes are trimmed, so `/todos/` and `/todos` are the same route.
+            .wrap(NormalizePath::trim())
+            .wrap(schema_version_header())
+            .wrap(from_fn(envelope))
+            .app_data(web::Data::new(server_state.clone()))
+            .service(inbox)
+            .service(get_lock_stats)
+            .service(get_labels)
+            .service(
+                web::scope("/todos")
+                    .service(get_todos)
+                    // Registered ahead of the `/{id}` routes so these paths aren't taken for an id.
+                    .service(get_focus)
+                    .service(get_todos_by_title)
+                    .service(get_done_log)
+                    .service(clear_focus)
+                    .service(set_focus)
+                    .service(archive_as_note)
+                    .service(reopen_todo)
+                    .service(restore_todo)
+                    .service(get_todo)
+                    .service(create_todo)
+                    .service(update_todo)
+                    .service(delete_todo)
+                    .service(get_todo_link)
+                    .service(link_todo)
+                    .service(unlink_todo)
+                    .service(batch_get)
+                    .service(bulk_uncomplete)
+                    .service(bulk_by_filter)
+                    .service(batch_text)
+                    .service(validate_todo)
+                    .service(import_items)
+                    .service(get_import_status)
+                    .service(import_csv)
+                    .service(import_todos)
+            )
+    })
+    .bind("127.0.0.1:8080")?
+    .run()
+    .await?;
+
+    if snapshots_enabled {
+        let config = &app_state.config;
+        snapshot::write(&config.snapshot_path, &app_state.snapshot(), config.snapshot_compress)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::StatusCode;
+    // `{self}` brings in only the module, so `#[test]` still means the built-in attribute.
+    use actix_web::test::{self};
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_get_empty_todos() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos))
+        ).await;
+        let req = test::TestRequest::get().uri("/todos").to_request();
+        let result = test::call_service(&app, req).await;
+        assert_eq!(result.status(), StatusCode::OK);
+
+        let expected: Vec<Todo> = Vec::new();
+        let expected_json = serde_json::to_string(&expected).unwrap();
+        let actual_json = test::read_body(result).await;
+        assert_eq!(actual_json, expected_json);
+    }
+
+    #[actix_web::test]
+    async fn test_get_todos() {
+        let v = vec![Todo::new("title".to_string())];
+        let app_state = Arc::new(AppState::new(v, Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos))
+        ).await;
+        let req = test::TestRequest::get().uri("/todos").to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let todos: Vec<Todo> = test::read_body_json(resp).await;
+        assert_eq!(todos.len(), 1);
+    }
+
+    #[actix_web::test]
     async fn test_create_todo() {
-        let app_state = Arc::new(AppState {
-            todos: Mutex::new(Vec::new()),
-        });
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
 
         let app = test::init_service(
             App::new()
@@ -197,23 +1410,15 @@ mod tests {
 
         let todo: Todo = test::read_body_json(resp).await;
         assert_eq!(todo.title, "test");
-        assert_eq!(todo.completed, false);
+        assert!(!todo.completed);
         assert!(!todo.id.is_empty());
     }
 
     #[actix_web::test]
     async fn test_update_todo() {
-        let id = Uuid::new_v4().to_string();
-        let old_todo = Todo {
-            id,
-            title: "title".to_string(),
-            completed: false,
-        }; 
-        let mut v = Vec::new();
-        v.push(old_todo.clone());
-        let app_state = Arc::new(AppState {
-            todos: Mutex::new(v),
-        });
+        let old_todo = Todo::new("title".to_string());
+        let v = vec![old_todo.clone()];
+        let app_state = Arc::new(AppState::new(v, Config::default()));
         let app = test::init_service(
             App::new()
                 .app_data(web::Data::new(app_state.clone()))
@@ -233,4 +1438,1368 @@ mod tests {
         let todo: Todo = test::read_body_json(resp).await;
         assert_eq!(todo.title, "new");
     }
+
+    #[actix_web::test]
+    async fn test_create_todo_gzip_body() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+        use std::io::Write;
+
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(create_todo))
+        ).await;
+
+        let todo_request = CreateTodoRequest {
+            title: "compressed".to_string(),
+        };
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&serde_json::to_vec(&todo_request).unwrap()).unwrap();
+        let body = encoder.finish().unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .insert_header(("Content-Type", "application/json"))
+            .insert_header(("Content-Encoding", "gzip"))
+            .set_payload(body)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let todo: Todo = test::read_body_json(resp).await;
+        assert_eq!(todo.title, "compressed");
+        assert_eq!(app_state.todos.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_create_todo_strict_fields_rejects_unknown_field() {
+        let config = Config {
+            strict_fields: true,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(create_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .set_json(serde_json::json!({ "title": "test", "titel": "typo" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let message: String = test::read_body_json(resp).await;
+        assert!(message.contains("titel"));
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_create_todo_lenient_ignores_unknown_field() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(create_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .set_json(serde_json::json!({ "title": "test", "titel": "typo" }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+    }
+
+    #[actix_web::test]
+    async fn test_link_and_unlink_todos() {
+        let a = Todo::new("a".to_string());
+        let b = Todo::new("b".to_string());
+        let app_state = Arc::new(AppState::new(vec![a.clone(), b.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(link_todo).service(unlink_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/link/{}", a.id, b.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        {
+            let todos = app_state.todos.lock().unwrap();
+            assert_eq!(todos[0].related, vec![b.id.clone()]);
+            assert_eq!(todos[1].related, vec![a.id.clone()]);
+        }
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}/link/{}", a.id, b.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let todos = app_state.todos.lock().unwrap();
+        assert!(todos[0].related.is_empty());
+        assert!(todos[1].related.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_link_todo_not_found() {
+        let a = Todo::new("a".to_string());
+        let app_state = Arc::new(AppState::new(vec![a.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(link_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/link/missing", a.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(app_state.todos.lock().unwrap()[0].related.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_import_todoist() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_todos))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/todoist")
+            .set_json(serde_json::json!({
+                "projects": [{ "id": "1", "name": "Inbox" }],
+                "items": [
+                    { "id": "10", "content": "Buy milk", "checked": false, "labels": ["groceries"] },
+                    { "id": "11", "content": "Call mom", "checked": true, "project_id": "1" }
+                ]
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body["unmapped_fields"],
+            serde_json::json!(["items.id", "items.labels", "items.project_id", "projects"])
+        );
+        let todos = app_state.todos.lock().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].title, "Buy milk");
+        assert!(!todos[0].completed);
+        assert_eq!(todos[1].title, "Call mom");
+        assert!(todos[1].completed);
+    }
+
+    #[actix_web::test]
+    async fn test_import_trello() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_todos))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/trello")
+            .set_json(serde_json::json!({
+                "lists": [{ "id": "l1", "name": "Doing" }],
+                "cards": [
+                    { "name": "Write report", "dueComplete": true, "idList": "l1" },
+                    { "name": "Review PR" }
+                ]
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["unmapped_fields"], serde_json::json!(["cards.idList", "lists"]));
+        let todos = app_state.todos.lock().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].title, "Write report");
+        assert!(todos[0].completed);
+        assert_eq!(todos[1].title, "Review PR");
+        assert!(!todos[1].completed);
+    }
+
+    #[actix_web::test]
+    async fn test_imports_sanitize_titles() {
+        let config = Config {
+            sanitize_titles: true,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(
+                    web::scope("/todos")
+                        .service(import_items)
+                        .service(import_csv)
+                        .service(import_todos)
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/todoist")
+            .set_json(serde_json::json!({ "items": [{ "content": "  Buy\u{200B}  milk " }] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/csv")
+            .set_payload("title\nCall\u{FEFF} mom\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import")
+            .set_json(serde_json::json!([{ "title": "Pay\u{2060}\trent" }]))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let titles: Vec<String> = app_state.todos.lock().unwrap().iter().map(|t| t.title.clone()).collect();
+        assert_eq!(titles, vec!["Buy milk", "Call mom", "Pay rent"]);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/trello")
+            .set_json(serde_json::json!({ "cards": [{ "name": "\u{200B}" }] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let message: String = test::read_body_json(resp).await;
+        assert_eq!(message, "cards[0].name: Title must not be empty");
+        assert_eq!(app_state.todos.lock().unwrap().len(), 3);
+    }
+
+    #[actix_web::test]
+    async fn test_import_rejects_invalid_payload() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_todos))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/trello")
+            .set_json(serde_json::json!({ "cards": [{ "name": "ok" }, { "desc": "no name" }] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/todoist")
+            .set_json(serde_json::json!({ "items": [{ "content": "Buy milk", "checked": 1 }] }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let message: String = test::read_body_json(resp).await;
+        assert_eq!(message, "items[0].checked must be a boolean");
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/asana")
+            .set_json(serde_json::json!({}))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_set_get_and_clear_focus() {
+        let todo = Todo::new("deep work".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(
+                    web::scope("/todos")
+                        .service(get_focus)
+                        .service(clear_focus)
+                        .service(set_focus)
+                )
+        ).await;
+
+        let req = test::TestRequest::get().uri("/todos/focus").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/focus", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/todos/focus").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let focused: Todo = test::read_body_json(resp).await;
+        assert_eq!(focused.id, todo.id);
+
+        let req = test::TestRequest::delete().uri("/todos/focus").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(app_state.focus.lock().unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_set_focus_not_found() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(set_focus))
+        ).await;
+
+        let req = test::TestRequest::post().uri("/todos/missing/focus").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(app_state.focus.lock().unwrap().is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_create_todo_sanitizes_title() {
+        let config = Config {
+            sanitize_titles: true,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(create_todo))
+        ).await;
+
+        let todo_request = CreateTodoRequest {
+            title: " Buy\tmilk\u{200B} and\0 eggs  ".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/todos")
+            .set_json(&todo_request)
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let todo: Todo = test::read_body_json(resp).await;
+        assert_eq!(todo.title, "Buy milk and eggs");
+    }
+
+    #[test]
+    fn test_sanitize_title_keeps_joiners() {
+        assert_eq!(sanitize_title("coding \u{1F469}\u{200D}\u{1F4BB}"), "coding \u{1F469}\u{200D}\u{1F4BB}");
+        assert_eq!(sanitize_title("\u{0645}\u{06CC}\u{200C}\u{062E}\u{0648}\u{0627}\u{0647}\u{0645}"), "\u{0645}\u{06CC}\u{200C}\u{062E}\u{0648}\u{0627}\u{0647}\u{0645}");
+    }
+
+    #[actix_web::test]
+    async fn test_get_todo_link() {
+        let todo = Todo::new("share me".to_string());
+        let config = Config {
+            app_base_url: "https://todo.example.com/".to_string(),
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todo_link))
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/todos/{}/link", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let link: TodoLink = test::read_body_json(resp).await;
+        assert_eq!(link.id, todo.id);
+        assert_eq!(link.url, format!("https://todo.example.com/todos/{}", todo.id));
+
+        let req = test::TestRequest::get().uri("/todos/missing/link").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_schema_version_header() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .wrap(schema_version_header())
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos).service(get_todo))
+        ).await;
+
+        let req = test::TestRequest::get().uri("/todos").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.headers().get("X-Schema-Version").unwrap(), SCHEMA_VERSION);
+
+        let req = test::TestRequest::get().uri("/todos/missing").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers().get("X-Schema-Version").unwrap(), SCHEMA_VERSION);
+    }
+
+    #[actix_web::test]
+    async fn test_get_todo_with_selection() {
+        let todo = Todo::new("title".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todo))
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/todos/{}?select=id,title", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body, serde_json::json!({ "id": todo.id, "title": "title" }));
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/todos/{}?select=id,owner", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn test_trailing_slash_is_trimmed() {
+        let todo = Todo::new("title".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .wrap(NormalizePath::trim())
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos).service(get_todo))
+        ).await;
+
+        for uri in ["/todos", "/todos/"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let todos: Vec<Todo> = test::read_body_json(resp).await;
+            assert_eq!(todos.len(), 1);
+        }
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/todos/{}/", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let found: Todo = test::read_body_json(resp).await;
+        assert_eq!(found.id, todo.id);
+    }
+
+    #[actix_web::test]
+    async fn test_import_csv() {
+        let existing = Todo::new("old title".to_string());
+        let app_state = Arc::new(AppState::new(vec![existing.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_csv).service(import_todos))
+        ).await;
+
+        let body = format!(
+            "id,title,completed\n{},\"Renamed, with comma\",true\n,\"Say \"\"hi\"\"\",\n,Plain,false\n",
+            existing.id
+        );
+        let req = test::TestRequest::post()
+            .uri("/todos/import/csv")
+            .insert_header(("Content-Type", "text/csv"))
+            .set_payload(body)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let todos = app_state.todos.lock().unwrap();
+        assert_eq!(todos.len(), 3);
+        assert_eq!(todos[0].id, existing.id);
+        assert_eq!(todos[0].title, "Renamed, with comma");
+        assert!(todos[0].completed);
+        assert_eq!(todos[1].title, "Say \"hi\"");
+        assert!(!todos[1].completed);
+        assert_eq!(todos[2].title, "Plain");
+    }
+
+    #[actix_web::test]
+    async fn test_import_csv_rejects_unknown_id() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_csv))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/csv")
+            .set_payload("id,title\n,fine\nfocus,Shadows a route\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let message: String = test::read_body_json(resp).await;
+        assert_eq!(message, "Row 2: todo with id focus not found");
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_import_csv_reports_bad_row() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_csv))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/csv")
+            .set_payload("title,completed\n\"fine,\nspanning two lines\",true\nbad,maybe\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let message: String = test::read_body_json(resp).await;
+        assert!(message.starts_with("Row 2:"), "{}", message);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_bulk_uncomplete() {
+        let mut todos: Vec<Todo> = (0..3).map(|i| Todo::new(format!("todo {}", i))).collect();
+        for todo in todos.iter_mut() {
+            todo.completed = true;
+        }
+        let app_state = Arc::new(AppState::new(todos.clone(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(bulk_uncomplete))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk-uncomplete")
+            .set_json(BulkIdsRequest {
+                ids: vec![todos[0].id.clone(), todos[2].id.clone(), "missing".to_string()],
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: BulkCountResponse = test::read_body_json(resp).await;
+        assert_eq!(body.count, 2);
+        let todos = app_state.todos.lock().unwrap();
+        assert!(!todos[0].completed);
+        assert!(todos[1].completed);
+        assert!(!todos[2].completed);
+    }
+
+    #[actix_web::test]
+    async fn test_inbox_creates_todo_from_text() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(inbox)
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/inbox")
+            .insert_header(("Content-Type", "text/plain"))
+            .set_payload("  Buy milk\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let todo: Todo = test::read_body_json(resp).await;
+        assert_eq!(todo.title, "Buy milk");
+        assert_eq!(app_state.todos.lock().unwrap().len(), 1);
+
+        let req = test::TestRequest::post()
+            .uri("/inbox")
+            .insert_header(("Content-Type", "text/plain"))
+            .set_payload("   ")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app_state.todos.lock().unwrap().len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_delete_todo_returns_representation() {
+        let todos = vec![Todo::new("first".to_string()), Todo::new("second".to_string())];
+        let app_state = Arc::new(AppState::new(todos.clone(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(delete_todo))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}?return=representation", todos[0].id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let deleted: Todo = test::read_body_json(resp).await;
+        assert_eq!(deleted.id, todos[0].id);
+        assert_eq!(deleted.title, "first");
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}", todos[1].id))
+            .insert_header(("Prefer", "return=representation"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let deleted: Todo = test::read_body_json(resp).await;
+        assert_eq!(deleted.id, todos[1].id);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_delete_todo_defaults_to_no_content() {
+        let todo = Todo::new("title".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(delete_todo))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_lock_stats_record_contention() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(get_lock_stats)
+        ).await;
+
+        let guard = app_state.todos.lock().unwrap();
+        let ready = Arc::new(std::sync::Barrier::new(2));
+        let waiter = {
+            let app_state = app_state.clone();
+            let ready = ready.clone();
+            std::thread::spawn(move || {
+                ready.wait();
+                drop(app_state.lock_todos());
+            })
+        };
+        // The waiter is about to lock; give it a moment to block on the guard.
+        ready.wait();
+        std::thread::sleep(Duration::from_millis(10));
+        drop(guard);
+        waiter.join().unwrap();
+
+        let req = test::TestRequest::get().uri("/stats/locks").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let stats: LockStatsResponse = test::read_body_json(resp).await;
+        assert_eq!(stats.acquisitions, 1);
+        assert!(stats.total_wait_ns > 0, "{:?}", stats);
+        assert_eq!(stats.max_wait_ns, stats.total_wait_ns);
+    }
+
+    #[actix_web::test]
+    async fn test_batch_text() {
+        let existing = Todo::new("existing".to_string());
+        let doomed = Todo::new("doomed".to_string());
+        let app_state = Arc::new(AppState::new(vec![existing.clone(), doomed.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(batch_text))
+        ).await;
+
+        let script = format!(
+            "create Buy milk\ncomplete {}\n\ndelete {}\nrename {}\ncomplete missing\n",
+            existing.id, doomed.id, existing.id
+        );
+        let req = test::TestRequest::post()
+            .uri("/todos/batch-text")
+            .insert_header(("Content-Type", "text/plain"))
+            .set_payload(script)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let results: Vec<BatchLineResult> = test::read_body_json(resp).await;
+        let lines: Vec<(usize, bool)> = results.iter().map(|r| (r.line, r.ok)).collect();
+        assert_eq!(lines, vec![(1, true), (2, true), (4, true), (5, false), (6, false)]);
+        assert_eq!(results[1].id.as_deref(), Some(existing.id.as_str()));
+        assert_eq!(results[3].error.as_deref(), Some("Unknown command rename"));
+
+        let todos = app_state.todos.lock().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert!(todos[0].completed);
+        assert_eq!(todos[1].title, "Buy milk");
+        assert_eq!(results[0].id.as_deref(), Some(todos[1].id.as_str()));
+    }
+
+    #[actix_web::test]
+    async fn test_bulk_size_limit() {
+        let config = Config {
+            max_bulk_size: 2,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(
+                    web::scope("/todos")
+                        .service(bulk_uncomplete)
+                        .service(batch_text)
+                        .service(import_csv)
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk-uncomplete")
+            .set_json(BulkIdsRequest {
+                ids: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let message: String = test::read_body_json(resp).await;
+        assert_eq!(message, "Bulk requests are limited to 2 items, got 3");
+
+        let req = test::TestRequest::post()
+            .uri("/todos/batch-text")
+            .set_payload("create a\ncreate b\ncreate c\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import/csv")
+            .set_payload("title\na\nb\n")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        assert_eq!(app_state.todos.lock().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_envelope_wraps_response() {
+        let todo = Todo::new("title".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(envelope))
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos).service(get_todo))
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/todos/{}?envelope=true", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["api_version"], SCHEMA_VERSION);
+        assert_eq!(body["data"]["id"], todo.id);
+
+        let req = test::TestRequest::get()
+            .uri("/todos")
+            .insert_header(("Accept", "application/json; envelope=true"))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+
+        let req = test::TestRequest::get().uri("/todos").to_request();
+        let resp = test::call_service(&app, req).await;
+        let todos: Vec<Todo> = test::read_body_json(resp).await;
+        assert_eq!(todos.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_validate_todo() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(validate_todo).service(create_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/validate")
+            .set_json(serde_json::json!({ "title": "fine" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: ValidationResponse = test::read_body_json(resp).await;
+        assert!(body.valid);
+
+        for payload in [serde_json::json!({ "title": "  " }), serde_json::json!({ "name": "x" })] {
+            let req = test::TestRequest::post()
+                .uri("/todos/validate")
+                .set_json(&payload)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body: ValidationResponse = test::read_body_json(resp).await;
+            assert!(!body.valid);
+            assert_eq!(body.errors.len(), 1);
+
+            let req = test::TestRequest::post()
+                .uri("/todos")
+                .set_json(&payload)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_bulk_delete_by_filter() {
+        let mut todos: Vec<Todo> = (0..3).map(|i| Todo::new(format!("todo {}", i))).collect();
+        todos[0].completed = true;
+        todos[2].completed = true;
+        let app_state = Arc::new(AppState::new(todos.clone(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(bulk_by_filter))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk")
+            .set_json(serde_json::json!({ "where": {}, "action": "delete" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app_state.todos.lock().unwrap().len(), 3);
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk")
+            .set_json(serde_json::json!({ "where": { "completed": true }, "action": "delete" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: BulkCountResponse = test::read_body_json(resp).await;
+        assert_eq!(body.count, 2);
+
+        let remaining = app_state.todos.lock().unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id, todos[1].id);
+    }
+
+    #[actix_web::test]
+    async fn test_bulk_complete_all_with_confirm() {
+        let todos: Vec<Todo> = (0..2).map(|i| Todo::new(format!("todo {}", i))).collect();
+        let app_state = Arc::new(AppState::new(todos, Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(bulk_by_filter))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk?confirm=true")
+            .set_json(serde_json::json!({ "where": {}, "action": "complete" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert!(app_state.todos.lock().unwrap().iter().all(|t| t.completed));
+
+        let req = test::TestRequest::post()
+            .uri("/todos/bulk?confirm=true")
+            .set_json(serde_json::json!({ "where": { "tag": "old" }, "action": "delete" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(app_state.todos.lock().unwrap().len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_get_todos_by_title() {
+        let todos = vec![Todo::new("Buy milk".to_string()), Todo::new("Call mom".to_string())];
+        let app_state = Arc::new(AppState::new(todos.clone(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos_by_title).service(get_todo))
+        ).await;
+
+        let req = test::TestRequest::get()
+            .uri("/todos/by-title?title=Buy%20milk")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let found: Vec<Todo> = test::read_body_json(resp).await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, todos[0].id);
+
+        let req = test::TestRequest::get()
+            .uri("/todos/by-title?title=%20buy%20%20MILK")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let found: Vec<Todo> = test::read_body_json(resp).await;
+        assert_eq!(found[0].id, todos[0].id);
+
+        let req = test::TestRequest::get()
+            .uri("/todos/by-title?title=nothing")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn test_archive_as_note() {
+        let mut done = Todo::new("shipped it".to_string());
+        done.completed = true;
+        let open = Todo::new("still open".to_string());
+        let app_state = Arc::new(AppState::new(vec![done.clone(), open.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(
+                    web::scope("/todos")
+                        .service(get_done_log)
+                        .service(archive_as_note)
+                )
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/archive-as-note", open.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/archive-as-note", done.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let entry: DoneLogEntry = test::read_body_json(resp).await;
+        assert_eq!(entry.todo_id, done.id);
+        assert_eq!(entry.title, "shipped it");
+
+        {
+            let todos = app_state.todos.lock().unwrap();
+            assert_eq!(todos.len(), 1);
+            assert_eq!(todos[0].id, open.id);
+        }
+
+        let req = test::TestRequest::get().uri("/todos/done-log").to_request();
+        let resp = test::call_service(&app, req).await;
+        let log: Vec<DoneLogEntry> = test::read_body_json(resp).await;
+        assert_eq!(log.len(), 1);
+        assert_eq!(log[0].todo_id, done.id);
+    }
+
+    #[actix_web::test]
+    async fn test_async_import_reports_progress() {
+        let config = Config {
+            max_bulk_size: 20,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_items).service(get_import_status))
+        ).await;
+
+        let count = IMPORT_CHUNK_SIZE * 2 + 7;
+        let items: Vec<ImportItem> = (0..count)
+            .map(|i| ImportItem { title: format!("todo {}", i), completed: false })
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/todos/import?async=true")
+            .set_json(&items)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+        let started: ImportJobStarted = test::read_body_json(resp).await;
+
+        let mut job = None;
+        for _ in 0..100 {
+            let req = test::TestRequest::get()
+                .uri(&format!("/todos/import/{}/status", started.job_id))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let status: ImportJob = test::read_body_json(resp).await;
+            assert_eq!(status.total, count);
+            if status.status == ImportStatus::Completed {
+                job = Some(status);
+                break;
+            }
+            actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert_eq!(job.expect("import did not complete").processed, count);
+        assert_eq!(app_state.todos.lock().unwrap().len(), count);
+    }
+
+    #[actix_web::test]
+    async fn test_sync_import_respects_bulk_limit() {
+        let config = Config {
+            max_bulk_size: 1,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_items))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/import")
+            .set_json(serde_json::json!([{ "title": "a" }, { "title": "b" }]))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_get_labels() {
+        let config = Config {
+            status_labels: StatusLabels::parse("completed=Done, incomplete=To do"),
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(get_labels)
+        ).await;
+
+        let req = test::TestRequest::get().uri("/labels").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let labels: StatusLabels = test::read_body_json(resp).await;
+        assert_eq!(labels.completed, "Done");
+        assert_eq!(labels.incomplete, "To do");
+
+        assert_eq!(StatusLabels::parse("closed=Closed,completed="), StatusLabels::default());
+    }
+
+    #[actix_web::test]
+    async fn test_batch_get() {
+        let todos: Vec<Todo> = (0..3).map(|i| Todo::new(format!("todo {}", i))).collect();
+        let app_state = Arc::new(AppState::new(todos.clone(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(batch_get))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri("/todos/batch-get")
+            .set_json(BulkIdsRequest {
+                ids: vec![todos[2].id.clone(), "missing".to_string(), todos[0].id.clone()],
+            })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body: BatchGetResponse = test::read_body_json(resp).await;
+        let ids: Vec<&str> = body.todos.iter().map(|t| t.id.as_str()).collect();
+        assert_eq!(ids, vec![todos[2].id.as_str(), todos[0].id.as_str()]);
+        assert_eq!(body.not_found, vec!["missing".to_string()]);
+    }
+
+    #[actix_web::test]
+    async fn test_get_todos_order_is_stable() {
+        let app_state = Arc::new(AppState::new(Vec::new(), Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(get_todos).service(create_todo))
+        ).await;
+
+        let mut created = Vec::new();
+        for i in 0..5 {
+            let req = test::TestRequest::post()
+                .uri("/todos")
+                .set_json(CreateTodoRequest { title: format!("todo {}", i) })
+                .to_request();
+            let todo: Todo = test::call_and_read_body_json(&app, req).await;
+            created.push(todo.id);
+        }
+
+        for _ in 0..3 {
+            let req = test::TestRequest::get().uri("/todos").to_request();
+            let todos: Vec<Todo> = test::call_and_read_body_json(&app, req).await;
+            let ids: Vec<String> = todos.into_iter().map(|t| t.id).collect();
+            assert_eq!(ids, created);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_reopen_todo_records_reason() {
+        let mut todo = Todo::new("title".to_string());
+        todo.completed = true;
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(reopen_todo))
+        ).await;
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/reopen", todo.id))
+            .set_json(ReopenRequest { reason: Some("missed a step".to_string()) })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let reopened: Todo = test::read_body_json(resp).await;
+        assert!(!reopened.completed);
+        assert_eq!(reopened.history, vec!["Reopened: missed a step".to_string()]);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/reopen", todo.id))
+            .set_json(ReopenRequest { reason: None })
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+        assert_eq!(app_state.todos.lock().unwrap()[0].history.len(), 1);
+
+        app_state.todos.lock().unwrap()[0].completed = true;
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/reopen", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let reopened: Todo = test::read_body_json(resp).await;
+        assert!(!reopened.completed);
+        assert_eq!(reopened.history.last().map(String::as_str), Some("Reopened"));
+    }
+
+    #[actix_web::test]
+    async fn test_reopen_todo_rejects_bad_body() {
+        let mut todo = Todo::new("title".to_string());
+        todo.completed = true;
+        let config = Config {
+            strict_fields: true,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(reopen_todo))
+        ).await;
+
+        for body in [r#"{"reason": "#, r#"{"reason": 42}"#, r#"{"reson": "typo"}"#] {
+            let req = test::TestRequest::post()
+                .uri(&format!("/todos/{}/reopen", todo.id))
+                .insert_header(("Content-Type", "application/json"))
+                .set_payload(body)
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "body {}", body);
+        }
+
+        let todos = app_state.todos.lock().unwrap();
+        assert!(todos[0].completed);
+        assert!(todos[0].history.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_update_todo_rejects_empty_title() {
+        let todo = Todo::new("title".to_string());
+        let config = Config {
+            sanitize_titles: true,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(update_todo))
+        ).await;
+
+        for title in ["", "\u{200B}"] {
+            let req = test::TestRequest::put()
+                .uri(&format!("/todos/{}", todo.id))
+                .set_json(UpdateTodoRequest { title: title.to_string() })
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        }
+        assert_eq!(app_state.todos.lock().unwrap()[0].title, "title");
+    }
+
+    #[actix_web::test]
+    async fn test_async_import_size_limit() {
+        let config = Config {
+            max_bulk_size: 1,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(Vec::new(), config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(import_items))
+        ).await;
+
+        let items: Vec<ImportItem> = (0..ASYNC_IMPORT_BULK_MULTIPLIER + 1)
+            .map(|i| ImportItem { title: format!("todo {}", i), completed: false })
+            .collect();
+        let req = test::TestRequest::post()
+            .uri("/todos/import?async=true")
+            .set_json(&items)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let message: String = test::read_body_json(resp).await;
+        assert_eq!(
+            message,
+            format!("Bulk requests are limited to {} items, got {}", ASYNC_IMPORT_BULK_MULTIPLIER, items.len())
+        );
+        assert!(app_state.import_jobs.lock().unwrap().jobs.is_empty());
+    }
+
+    #[test]
+    fn test_finished_import_jobs_are_evicted() {
+        let mut import_jobs = ImportJobs::default();
+        let ids: Vec<String> = (0..MAX_FINISHED_IMPORT_JOBS + 2).map(|i| format!("job {}", i)).collect();
+        for id in &ids {
+            import_jobs.jobs.insert(
+                id.clone(),
+                ImportJob { status: ImportStatus::Running, total: 1, processed: 1 },
+            );
+        }
+        for id in &ids[..ids.len() - 1] {
+            import_jobs.finish(id);
+        }
+
+        assert_eq!(import_jobs.jobs.len(), MAX_FINISHED_IMPORT_JOBS + 1);
+        assert!(!import_jobs.jobs.contains_key(&ids[0]));
+        assert_eq!(import_jobs.jobs[&ids[1]].status, ImportStatus::Completed);
+        assert_eq!(import_jobs.jobs[ids.last().unwrap()].status, ImportStatus::Running);
+    }
+
+    #[actix_web::test]
+    async fn test_restore_within_undo_window() {
+        let a = Todo::new("a".to_string());
+        let mut b = Todo::new("b".to_string());
+        let mut a_linked = a.clone();
+        a_linked.related.push(b.id.clone());
+        b.related.push(a.id.clone());
+        let config = Config {
+            undo_window_secs: 60,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(vec![a_linked, b.clone()], config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(delete_todo).service(restore_todo))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}", a.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert!(app_state.todos.lock().unwrap()[0].related.is_empty());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/restore", a.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let restored: Todo = test::read_body_json(resp).await;
+        assert_eq!(restored.id, a.id);
+        assert_eq!(restored.related, vec![b.id.clone()]);
+
+        let todos = app_state.todos.lock().unwrap();
+        assert_eq!(todos.len(), 2);
+        assert_eq!(todos[0].related, vec![a.id.clone()]);
+        assert!(app_state.tombstones.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_tombstones_expire_after_undo_window() {
+        let todo = Todo::new("title".to_string());
+        let config = Config {
+            undo_window_secs: 30,
+            ..Config::default()
+        };
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], config));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(delete_todo).service(restore_todo))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}", todo.id))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let deleted_at = app_state.tombstones.lock().unwrap()[0].deleted_at;
+        assert_eq!(app_state.purge_tombstones(deleted_at + Duration::from_secs(29)), 0);
+        assert_eq!(app_state.purge_tombstones(deleted_at + Duration::from_secs(31)), 1);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/restore", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert!(app_state.todos.lock().unwrap().is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_delete_without_undo_window_is_permanent() {
+        let todo = Todo::new("title".to_string());
+        let app_state = Arc::new(AppState::new(vec![todo.clone()], Config::default()));
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(app_state.clone()))
+                .service(web::scope("/todos").service(delete_todo).service(restore_todo))
+        ).await;
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/todos/{}", todo.id))
+            .to_request();
+        test::call_service(&app, req).await;
+        assert!(app_state.tombstones.lock().unwrap().is_empty());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/todos/{}/restore", todo.id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
 }
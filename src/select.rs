@@ -0,0 +1,149 @@
+use serde_json::{Map, Value};
+
+/// One requested field, optionally with a nested selection of its own fields.
+#[derive(Debug, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub children: Vec<Field>,
+}
+
+/// How deeply `{}` selections may nest. The parser recurses once per level, so
+/// this bounds stack use for hostile input like `a{a{a{...`.
+const MAX_DEPTH: usize = 8;
+
+/// Parses a selection like `id,title,subtasks{id,title}`.
+pub fn parse(input: &str) -> Result<Vec<Field>, String> {
+    let mut chars = input.chars().peekable();
+    let fields = parse_fields(&mut chars, 0)?;
+    match chars.next() {
+        None => Ok(fields),
+        Some(c) => Err(format!("Unexpected '{}' in selection", c)),
+    }
+}
+
+fn skip_whitespace(chars: &mut std::iter::Peekable<std::str::Chars>) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn is_name_char(c: &char) -> bool {
+    c.is_alphanumeric() || *c == '_'
+}
+
+fn parse_fields(chars: &mut std::iter::Peekable<std::str::Chars>, depth: usize) -> Result<Vec<Field>, String> {
+    let mut fields = Vec::new();
+    loop {
+        // Whitespace may surround a name but not split it.
+        skip_whitespace(chars);
+        let mut name = String::new();
+        while let Some(c) = chars.next_if(is_name_char) {
+            name.push(c);
+        }
+        if name.is_empty() {
+            return Err("Empty field name in selection".to_string());
+        }
+        skip_whitespace(chars);
+        if chars.peek().is_some_and(is_name_char) {
+            return Err(format!("Unexpected whitespace in field name {}", name));
+        }
+
+        let mut children = Vec::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            if depth >= MAX_DEPTH {
+                return Err(format!("Selection nested deeper than {} levels", MAX_DEPTH));
+            }
+            children = parse_fields(chars, depth + 1)?;
+            if chars.next() != Some('}') {
+                return Err(format!("Unclosed '{{' after {}", name));
+            }
+            skip_whitespace(chars);
+        }
+        fields.push(Field { name, children });
+
+        if chars.peek() == Some(&',') {
+            chars.next();
+        } else {
+            return Ok(fields);
+        }
+    }
+}
+
+/// Projects `value` down to the selected fields. Selecting a field the value
+/// doesn't have, or sub-selecting a field that isn't an object, is an error.
+pub fn apply(fields: &[Field], value: &Value) -> Result<Value, String> {
+    let Some(object) = value.as_object() else {
+        return Err("Selection applied to a non-object".to_string());
+    };
+
+    let mut selected = Map::new();
+    for field in fields {
+        let Some(inner) = object.get(&field.name) else {
+            return Err(format!("Unknown field {}", field.name));
+        };
+        let projected = if field.children.is_empty() {
+            inner.clone()
+        } else {
+            match inner {
+                Value::Object(_) => apply(&field.children, inner)?,
+                Value::Array(items) if items.iter().all(Value::is_object) => Value::Array(
+                    items
+                        .iter()
+                        .map(|item| apply(&field.children, item))
+                        .collect::<Result<_, _>>()?,
+                ),
+                _ => return Err(format!("Field {} has no subfields to select", field.name)),
+            }
+        };
+        selected.insert(field.name.clone(), projected);
+    }
+
+    Ok(Value::Object(selected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_apply_nested_selection() {
+        let value = json!({
+            "id": "1",
+            "title": "parent",
+            "completed": false,
+            "subtasks": [
+                { "id": "2", "title": "child", "completed": true }
+            ]
+        });
+
+        let fields = parse("id, title, subtasks{id,title}").unwrap();
+        let selected = apply(&fields, &value).unwrap();
+
+        assert_eq!(
+            selected,
+            json!({ "id": "1", "title": "parent", "subtasks": [{ "id": "2", "title": "child" }] })
+        );
+    }
+
+    #[test]
+    fn test_invalid_selections() {
+        let value = json!({ "id": "1", "title": "t" });
+
+        assert!(parse("id,").is_err());
+        assert!(parse("subtasks{id").is_err());
+        assert!(parse("id}").is_err());
+        assert!(parse("ti tle").is_err());
+        assert!(parse(" id , title { id } ").is_ok());
+        assert!(apply(&parse("nope").unwrap(), &value).is_err());
+        assert!(apply(&parse("title{id}").unwrap(), &value).is_err());
+    }
+
+    #[test]
+    fn test_deep_nesting_is_rejected() {
+        let nested = |depth: usize| format!("{}id{}", "a{".repeat(depth), "}".repeat(depth));
+
+        assert!(parse(&nested(MAX_DEPTH)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH + 1)).is_err());
+        assert!(parse(&"a{".repeat(8000)).is_err());
+    }
+}
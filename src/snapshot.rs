@@ -0,0 +1,203 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+
+use crate::{AppState, DoneLogEntry, Todo, Tombstone};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// The persisted parts of the store. The done log is the only record left of
+/// todos archived as notes, and tombstones keep deletes undoable across a
+/// restart, so both are saved alongside the todos.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub todos: Vec<Todo>,
+    #[serde(default)]
+    pub done_log: Vec<DoneLogEntry>,
+    #[serde(default)]
+    pub tombstones: Vec<Tombstone>,
+}
+
+// Snapshots written before the done log was persisted are a bare todo array.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSnapshot {
+    Full(Snapshot),
+    TodosOnly(Vec<Todo>),
+}
+
+impl From<StoredSnapshot> for Snapshot {
+    fn from(stored: StoredSnapshot) -> Self {
+        match stored {
+            StoredSnapshot::Full(snapshot) => snapshot,
+            StoredSnapshot::TodosOnly(todos) => Snapshot { todos, ..Snapshot::default() },
+        }
+    }
+}
+
+/// Writes `snapshot` to `path` atomically: the data goes to a sibling temp file
+/// first and is renamed over the old snapshot, so readers never see a torn write.
+/// With `compress` set the JSON is gzipped.
+pub fn write(path: &Path, snapshot: &Snapshot, compress: bool) -> io::Result<()> {
+    let json = serde_json::to_vec(snapshot)?;
+    let bytes = if compress {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&json)?;
+        encoder.finish()?
+    } else {
+        json
+    };
+
+    let tmp_path = path.with_extension("tmp");
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(&bytes)?;
+    file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Loads the snapshot stored at `path`, or an empty one if none exists yet.
+/// Compressed and plain snapshots are told apart by the gzip magic bytes.
+pub fn load(path: &Path) -> io::Result<Snapshot> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Snapshot::default()),
+        Err(e) => return Err(e),
+    };
+
+    let stored: StoredSnapshot = if bytes.starts_with(&GZIP_MAGIC) {
+        let mut json = Vec::new();
+        GzDecoder::new(bytes.as_slice()).read_to_end(&mut json)?;
+        serde_json::from_slice(&json)?
+    } else {
+        serde_json::from_slice(&bytes)?
+    };
+    Ok(stored.into())
+}
+
+/// Snapshots the store every `interval` until the runtime shuts down.
+pub fn spawn(app_state: Arc<AppState>, interval: Duration) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval);
+        // The first tick completes immediately; skip it so we don't rewrite what was just loaded.
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let snapshot = app_state.snapshot();
+            let config = &app_state.config;
+            match write(&config.snapshot_path, &snapshot, config.snapshot_compress) {
+                Ok(()) => log::debug!("Wrote snapshot of {} todos", snapshot.todos.len()),
+                Err(e) => log::error!("Failed to write snapshot: {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Config;
+    use std::time::SystemTime;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_snapshot_restores_app_state() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let todos = vec![Todo::new("first".to_string()), Todo::new("second".to_string())];
+        let snapshot = Snapshot { todos: todos.clone(), ..Snapshot::default() };
+
+        write(&path, &snapshot, false).unwrap();
+        let app_state = AppState::from_snapshot(load(&path).unwrap(), Config::default());
+        fs::remove_file(&path).unwrap();
+
+        let restored = app_state.todos.lock().unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].id, todos[0].id);
+        assert_eq!(restored[1].title, "second");
+        assert!(!path.with_extension("tmp").exists());
+    }
+
+    #[test]
+    fn test_load_missing_snapshot_is_empty() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+
+        let snapshot = load(&path).unwrap();
+        assert!(snapshot.todos.is_empty());
+        assert!(snapshot.done_log.is_empty());
+    }
+
+    #[test]
+    fn test_snapshot_restores_done_log() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let entry = DoneLogEntry {
+            todo_id: Uuid::new_v4().to_string(),
+            title: "archived".to_string(),
+        };
+        let snapshot = Snapshot { done_log: vec![entry.clone()], ..Snapshot::default() };
+
+        write(&path, &snapshot, false).unwrap();
+        let app_state = AppState::from_snapshot(load(&path).unwrap(), Config::default());
+        fs::remove_file(&path).unwrap();
+
+        let done_log = app_state.done_log.lock().unwrap();
+        assert_eq!(done_log.len(), 1);
+        assert_eq!(done_log[0].todo_id, entry.todo_id);
+        assert_eq!(done_log[0].title, "archived");
+    }
+
+    #[test]
+    fn test_snapshot_restores_tombstones() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let tombstone = Tombstone {
+            todo: Todo::new("deleted".to_string()),
+            deleted_at: SystemTime::now(),
+        };
+        let snapshot = Snapshot { tombstones: vec![tombstone.clone()], ..Snapshot::default() };
+
+        write(&path, &snapshot, false).unwrap();
+        let app_state = AppState::from_snapshot(load(&path).unwrap(), Config::default());
+        fs::remove_file(&path).unwrap();
+
+        let tombstones = app_state.tombstones.lock().unwrap();
+        assert_eq!(tombstones.len(), 1);
+        assert_eq!(tombstones[0].todo.id, tombstone.todo.id);
+        assert_eq!(tombstones[0].deleted_at, tombstone.deleted_at);
+    }
+
+    #[test]
+    fn test_load_todos_only_snapshot() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json", Uuid::new_v4()));
+        let todos = vec![Todo::new("first".to_string())];
+        fs::write(&path, serde_json::to_vec(&todos).unwrap()).unwrap();
+
+        let snapshot = load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(snapshot.todos.len(), 1);
+        assert_eq!(snapshot.todos[0].id, todos[0].id);
+        assert!(snapshot.done_log.is_empty());
+    }
+
+    #[test]
+    fn test_compressed_snapshot_round_trip() {
+        let path = std::env::temp_dir().join(format!("todos-{}.json.gz", Uuid::new_v4()));
+        let todos = vec![Todo::new("first".to_string()), Todo::new("second".to_string())];
+        let snapshot = Snapshot { todos: todos.clone(), ..Snapshot::default() };
+
+        write(&path, &snapshot, true).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let restored = load(&path).unwrap().todos;
+        fs::remove_file(&path).unwrap();
+
+        assert!(bytes.starts_with(&GZIP_MAGIC));
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored[0].id, todos[0].id);
+        assert_eq!(restored[1].title, todos[1].title);
+    }
+}